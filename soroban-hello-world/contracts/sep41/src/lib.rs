@@ -1,19 +1,26 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, String, Symbol,
+    contract, contracterror, contractimpl, contracttype, symbol_short, Address, Bytes, Env,
+    IntoVal, String, Symbol, Val, Vec,
 };
 use soroban_token_sdk::{metadata::TokenMetadata, TokenUtils};
 
+#[cfg(test)]
+mod test;
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     Allowance(Address, Address),
     Balance(Address),
     Admin,
+    TotalSupply,
+    Minters,
+    Permission(Address, Address),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-#[contracttype]
+#[contracterror]
 #[repr(u32)]
 pub enum ContractError {
     InternalError = 1,
@@ -30,28 +37,94 @@ pub struct AllowanceValue {
     pub expiration_ledger: u32,
 }
 
-fn check_nonnegative_amount(amount: i128) {
+#[derive(Clone)]
+#[contracttype]
+pub struct Permission {
+    pub limit: i128,
+    pub expiration_ledger: u32,
+    pub can_transfer: bool,
+    pub can_burn: bool,
+    pub can_approve: bool,
+}
+
+const DAY_IN_LEDGERS: u32 = 17280;
+
+const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_BUMP_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+const BALANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const BALANCE_BUMP_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+fn check_nonnegative_amount(amount: i128) -> Result<(), ContractError> {
     if amount < 0 {
-        panic!("negative amount");
+        Err(ContractError::NegativeAmountError)
+    } else {
+        Ok(())
     }
 }
 
 pub trait TokenTrait {
-    fn __constructor(e: Env, admin: Address, decimal: u32, name: String, symbol: String);
+    fn __constructor(
+        e: Env,
+        admin: Address,
+        decimal: u32,
+        name: String,
+        symbol: String,
+        initial_balances: Option<Vec<(Address, i128)>>,
+    ) -> Result<(), ContractError>;
 
     fn allowance(e: Env, from: Address, spender: Address) -> i128;
 
-    fn approve(e: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32);
+    fn approve(
+        e: Env,
+        from: Address,
+        spender: Address,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), ContractError>;
+
+    // Not part of SEP-41 or the original permission-set spec (only
+    // `grant_permission`/`revoke_permission`/`query_permissions` were
+    // called for); added so `Permission::can_approve` gates something
+    // observable instead of being a dead flag. Widens the public
+    // `TokenTrait` surface beyond what was originally scoped.
+    fn approve_from(
+        e: Env,
+        spender: Address,
+        owner: Address,
+        new_spender: Address,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), ContractError>;
 
     fn balance(e: Env, id: Address) -> i128;
 
-    fn transfer(e: Env, from: Address, to: Address, amount: i128);
-
-    fn transfer_from(e: Env, spender: Address, from: Address, to: Address, amount: i128);
-
-    fn burn(e: Env, from: Address, amount: i128);
-
-    fn burn_from(e: Env, spender: Address, from: Address, amount: i128);
+    fn transfer(e: Env, from: Address, to: Address, amount: i128) -> Result<(), ContractError>;
+
+    fn transfer_from(
+        e: Env,
+        spender: Address,
+        from: Address,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), ContractError>;
+
+    fn transfer_and_call(
+        e: Env,
+        from: Address,
+        to: Address,
+        amount: i128,
+        data: Bytes,
+    ) -> Result<i128, ContractError>;
+
+    fn burn(e: Env, from: Address, amount: i128) -> Result<(), ContractError>;
+
+    fn burn_from(
+        e: Env,
+        spender: Address,
+        from: Address,
+        amount: i128,
+    ) -> Result<(), ContractError>;
 
     fn decimals(e: Env) -> u32;
 
@@ -59,11 +132,32 @@ pub trait TokenTrait {
 
     fn symbol(e: Env) -> String;
 
-    fn mint(e: Env, to: Address, amount: i128);
+    fn mint(e: Env, minter: Address, to: Address, amount: i128) -> Result<(), ContractError>;
 
-    fn set_admin(e: Env, new_admin: Address);
+    fn set_admin(e: Env, new_admin: Address) -> Result<(), ContractError>;
 
     fn admin(e: Env) -> Address;
+
+    fn total_supply(e: Env) -> i128;
+
+    fn add_minter(e: Env, minter: Address) -> Result<(), ContractError>;
+
+    fn remove_minter(e: Env, minter: Address) -> Result<(), ContractError>;
+
+    fn grant_permission(
+        e: Env,
+        owner: Address,
+        spender: Address,
+        limit: i128,
+        expiration_ledger: u32,
+        can_transfer: bool,
+        can_burn: bool,
+        can_approve: bool,
+    ) -> Result<(), ContractError>;
+
+    fn revoke_permission(e: Env, owner: Address, spender: Address) -> Result<(), ContractError>;
+
+    fn query_permissions(e: Env, owner: Address, spender: Address) -> Option<Permission>;
 }
 
 #[contract]
@@ -71,14 +165,39 @@ pub struct Token;
 
 #[contractimpl]
 impl TokenTrait for Token {
-    fn __constructor(e: Env, admin: Address, decimal: u32, name: String, symbol: String) {
+    fn __constructor(
+        e: Env,
+        admin: Address,
+        decimal: u32,
+        name: String,
+        symbol: String,
+        initial_balances: Option<Vec<(Address, i128)>>,
+    ) -> Result<(), ContractError> {
         if e.storage().instance().has(&DataKey::Admin) {
-            panic!("already initialized");
+            return Err(ContractError::AlreadyInitializedError);
         }
 
         // Set admin
         e.storage().instance().set(&DataKey::Admin, &admin);
 
+        // The admin is a minter from the start; more can be added later.
+        let mut minters = Vec::new(&e);
+        minters.push_back(admin.clone());
+        write_minters(&e, &minters);
+
+        // Credit any initial balances and accumulate the total supply.
+        let mut total_supply: i128 = 0;
+        if let Some(balances) = initial_balances {
+            for (account, amount) in balances.iter() {
+                check_nonnegative_amount(amount)?;
+                total_supply = total_supply
+                    .checked_add(amount)
+                    .ok_or(ContractError::OverflowError)?;
+                receive_balance(&e, account, amount)?;
+            }
+        }
+        write_total_supply(&e, total_supply);
+
         // Set metadata
         let metadata = TokenMetadata {
             decimal,
@@ -88,60 +207,180 @@ impl TokenTrait for Token {
         e.storage()
             .instance()
             .set(&symbol_short!("METADATA"), &metadata);
+
+        e.storage()
+            .instance()
+            .extend_ttl(INSTANCE_BUMP_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+        Ok(())
     }
 
     fn allowance(e: Env, from: Address, spender: Address) -> i128 {
         read_allowance(&e, from, spender).amount
     }
 
-    fn approve(e: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+    fn approve(
+        e: Env,
+        from: Address,
+        spender: Address,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), ContractError> {
         from.require_auth();
-        check_nonnegative_amount(amount);
+        check_nonnegative_amount(amount)?;
 
         write_allowance(&e, from.clone(), spender.clone(), amount, expiration_ledger);
+        extend_instance_ttl(&e);
         TokenUtils::new(&e)
             .events()
             .approve(from, spender, amount, expiration_ledger);
+
+        Ok(())
+    }
+
+    fn approve_from(
+        e: Env,
+        spender: Address,
+        owner: Address,
+        new_spender: Address,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), ContractError> {
+        spender.require_auth();
+        check_nonnegative_amount(amount)?;
+
+        let permission =
+            read_permission(&e, owner.clone(), spender).ok_or(ContractError::UnauthorizedError)?;
+        if e.ledger().sequence() > permission.expiration_ledger || !permission.can_approve {
+            return Err(ContractError::UnauthorizedError);
+        }
+
+        write_allowance(
+            &e,
+            owner.clone(),
+            new_spender.clone(),
+            amount,
+            expiration_ledger,
+        );
+        extend_instance_ttl(&e);
+        TokenUtils::new(&e)
+            .events()
+            .approve(owner, new_spender, amount, expiration_ledger);
+
+        Ok(())
     }
 
     fn balance(e: Env, id: Address) -> i128 {
         read_balance(&e, id)
     }
 
-    fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+    fn transfer(e: Env, from: Address, to: Address, amount: i128) -> Result<(), ContractError> {
         from.require_auth();
-        check_nonnegative_amount(amount);
+        check_nonnegative_amount(amount)?;
 
-        spend_balance(&e, from.clone(), amount);
-        receive_balance(&e, to.clone(), amount);
+        spend_balance(&e, from.clone(), amount)?;
+        receive_balance(&e, to.clone(), amount)?;
+        extend_instance_ttl(&e);
         TokenUtils::new(&e).events().transfer(from, to, amount);
+
+        Ok(())
     }
 
-    fn transfer_from(e: Env, spender: Address, from: Address, to: Address, amount: i128) {
+    fn transfer_from(
+        e: Env,
+        spender: Address,
+        from: Address,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
         spender.require_auth();
-        check_nonnegative_amount(amount);
+        check_nonnegative_amount(amount)?;
 
-        spend_allowance(&e, from.clone(), spender, amount);
-        spend_balance(&e, from.clone(), amount);
-        receive_balance(&e, to.clone(), amount);
+        authorize_spend(&e, from.clone(), spender, amount, |p| p.can_transfer)?;
+        spend_balance(&e, from.clone(), amount)?;
+        receive_balance(&e, to.clone(), amount)?;
+        extend_instance_ttl(&e);
         TokenUtils::new(&e).events().transfer(from, to, amount);
+
+        Ok(())
+    }
+
+    fn transfer_and_call(
+        e: Env,
+        from: Address,
+        to: Address,
+        amount: i128,
+        data: Bytes,
+    ) -> Result<i128, ContractError> {
+        from.require_auth();
+        check_nonnegative_amount(amount)?;
+
+        spend_balance(&e, from.clone(), amount)?;
+        receive_balance(&e, to.clone(), amount)?;
+        extend_instance_ttl(&e);
+        TokenUtils::new(&e)
+            .events()
+            .transfer(from.clone(), to.clone(), amount);
+
+        let args: Vec<Val> = soroban_sdk::vec![
+            &e,
+            from.clone().into_val(&e),
+            amount.into_val(&e),
+            data.into_val(&e),
+        ];
+
+        let accepted = match e.try_invoke_contract::<i128, soroban_sdk::Error>(
+            &to,
+            &Symbol::new(&e, "on_token_received"),
+            args,
+        ) {
+            Ok(Ok(accepted)) => accepted.clamp(0, amount),
+            // The recipient trapped; returning an error here aborts this
+            // call frame, which rolls back the spend/receive above along
+            // with everything else this function did.
+            _ => return Err(ContractError::InternalError),
+        };
+
+        let refund = amount - accepted;
+        if refund > 0 {
+            spend_balance(&e, to.clone(), refund)?;
+            receive_balance(&e, from.clone(), refund)?;
+            extend_instance_ttl(&e);
+            e.events()
+                .publish((symbol_short!("refund"), from, to), refund);
+        }
+
+        Ok(accepted)
     }
 
-    fn burn(e: Env, from: Address, amount: i128) {
+    fn burn(e: Env, from: Address, amount: i128) -> Result<(), ContractError> {
         from.require_auth();
-        check_nonnegative_amount(amount);
+        check_nonnegative_amount(amount)?;
 
-        spend_balance(&e, from.clone(), amount);
+        spend_balance(&e, from.clone(), amount)?;
+        decrease_total_supply(&e, amount)?;
+        extend_instance_ttl(&e);
         TokenUtils::new(&e).events().burn(from, amount);
+
+        Ok(())
     }
 
-    fn burn_from(e: Env, spender: Address, from: Address, amount: i128) {
+    fn burn_from(
+        e: Env,
+        spender: Address,
+        from: Address,
+        amount: i128,
+    ) -> Result<(), ContractError> {
         spender.require_auth();
-        check_nonnegative_amount(amount);
+        check_nonnegative_amount(amount)?;
 
-        spend_allowance(&e, from.clone(), spender, amount);
-        spend_balance(&e, from.clone(), amount);
+        authorize_spend(&e, from.clone(), spender, amount, |p| p.can_burn)?;
+        spend_balance(&e, from.clone(), amount)?;
+        decrease_total_supply(&e, amount)?;
+        extend_instance_ttl(&e);
         TokenUtils::new(&e).events().burn(from, amount);
+
+        Ok(())
     }
 
     fn decimals(e: Env) -> u32 {
@@ -171,7 +410,25 @@ impl TokenTrait for Token {
         metadata.symbol
     }
 
-    fn mint(e: Env, to: Address, amount: i128) {
+    fn mint(e: Env, minter: Address, to: Address, amount: i128) -> Result<(), ContractError> {
+        minter.require_auth();
+        if !read_minters(&e).contains(&minter) {
+            return Err(ContractError::UnauthorizedError);
+        }
+
+        check_nonnegative_amount(amount)?;
+        receive_balance(&e, to.clone(), amount)?;
+        let total_supply = read_total_supply(&e)
+            .checked_add(amount)
+            .ok_or(ContractError::OverflowError)?;
+        write_total_supply(&e, total_supply);
+        extend_instance_ttl(&e);
+        TokenUtils::new(&e).events().mint(minter, to, amount);
+
+        Ok(())
+    }
+
+    fn set_admin(e: Env, new_admin: Address) -> Result<(), ContractError> {
         let admin = e
             .storage()
             .instance()
@@ -179,12 +436,25 @@ impl TokenTrait for Token {
             .unwrap();
         admin.require_auth();
 
-        check_nonnegative_amount(amount);
-        receive_balance(&e, to.clone(), amount);
-        TokenUtils::new(&e).events().mint(admin, to, amount);
+        e.storage().instance().set(&DataKey::Admin, &new_admin);
+        extend_instance_ttl(&e);
+        TokenUtils::new(&e).events().set_admin(admin, new_admin);
+
+        Ok(())
+    }
+
+    fn admin(e: Env) -> Address {
+        e.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Admin)
+            .unwrap()
+    }
+
+    fn total_supply(e: Env) -> i128 {
+        read_total_supply(&e)
     }
 
-    fn set_admin(e: Env, new_admin: Address) {
+    fn add_minter(e: Env, minter: Address) -> Result<(), ContractError> {
         let admin = e
             .storage()
             .instance()
@@ -192,30 +462,112 @@ impl TokenTrait for Token {
             .unwrap();
         admin.require_auth();
 
-        e.storage().instance().set(&DataKey::Admin, &new_admin);
-        TokenUtils::new(&e).events().set_admin(admin, new_admin);
+        let mut minters = read_minters(&e);
+        if !minters.contains(&minter) {
+            minters.push_back(minter);
+            write_minters(&e, &minters);
+        }
+        extend_instance_ttl(&e);
+
+        Ok(())
     }
 
-    fn admin(e: Env) -> Address {
-        e.storage()
+    fn remove_minter(e: Env, minter: Address) -> Result<(), ContractError> {
+        let admin = e
+            .storage()
             .instance()
             .get::<DataKey, Address>(&DataKey::Admin)
-            .unwrap()
+            .unwrap();
+        admin.require_auth();
+
+        let minters = read_minters(&e);
+        let mut remaining = Vec::new(&e);
+        for m in minters.iter() {
+            if m != minter {
+                remaining.push_back(m);
+            }
+        }
+        write_minters(&e, &remaining);
+        extend_instance_ttl(&e);
+
+        Ok(())
+    }
+
+    fn grant_permission(
+        e: Env,
+        owner: Address,
+        spender: Address,
+        limit: i128,
+        expiration_ledger: u32,
+        can_transfer: bool,
+        can_burn: bool,
+        can_approve: bool,
+    ) -> Result<(), ContractError> {
+        owner.require_auth();
+        check_nonnegative_amount(limit)?;
+
+        let permission = Permission {
+            limit,
+            expiration_ledger,
+            can_transfer,
+            can_burn,
+            can_approve,
+        };
+        write_permission(&e, owner, spender, &permission);
+        extend_instance_ttl(&e);
+
+        Ok(())
+    }
+
+    fn revoke_permission(e: Env, owner: Address, spender: Address) -> Result<(), ContractError> {
+        owner.require_auth();
+
+        remove_permission(&e, owner, spender);
+        extend_instance_ttl(&e);
+
+        Ok(())
+    }
+
+    fn query_permissions(e: Env, owner: Address, spender: Address) -> Option<Permission> {
+        read_permission(&e, owner, spender)
     }
 }
 
 // Helper functions
-fn write_allowance(e: &Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+fn extend_instance_ttl(e: &Env) {
+    e.storage()
+        .instance()
+        .extend_ttl(INSTANCE_BUMP_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
+fn extend_allowance_ttl(e: &Env, from: Address, spender: Address, expiration_ledger: u32) {
     let key = DataKey::Allowance(from, spender);
+    // An allowance's own expiration already bounds how long it needs to
+    // live, so there's no separate bump-amount constant here: just keep it
+    // alive for exactly as long as it remains valid.
+    let live_for = expiration_ledger.saturating_sub(e.ledger().sequence());
+    e.storage().persistent().extend_ttl(&key, live_for, live_for);
+}
+
+fn extend_balance_ttl(e: &Env, addr: Address) {
+    let key = DataKey::Balance(addr);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, BALANCE_BUMP_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+fn write_allowance(e: &Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+    let key = DataKey::Allowance(from.clone(), spender.clone());
     let allowance = AllowanceValue {
         amount,
         expiration_ledger,
     };
     e.storage().persistent().set(&key, &allowance);
+    extend_allowance_ttl(e, from, spender, expiration_ledger);
 }
 
 fn read_allowance(e: &Env, from: Address, spender: Address) -> AllowanceValue {
-    let key = DataKey::Allowance(from, spender);
+    let key = DataKey::Allowance(from.clone(), spender.clone());
     if let Some(allowance) = e
         .storage()
         .persistent()
@@ -227,6 +579,7 @@ fn read_allowance(e: &Env, from: Address, spender: Address) -> AllowanceValue {
                 expiration_ledger: allowance.expiration_ledger,
             }
         } else {
+            extend_allowance_ttl(e, from, spender, allowance.expiration_ledger);
             allowance
         }
     } else {
@@ -237,10 +590,15 @@ fn read_allowance(e: &Env, from: Address, spender: Address) -> AllowanceValue {
     }
 }
 
-fn spend_allowance(e: &Env, from: Address, spender: Address, amount: i128) {
+fn spend_allowance(
+    e: &Env,
+    from: Address,
+    spender: Address,
+    amount: i128,
+) -> Result<(), ContractError> {
     let allowance = read_allowance(e, from.clone(), spender.clone());
     if allowance.amount < amount {
-        panic!("insufficient allowance");
+        return Err(ContractError::BalanceError);
     }
     write_allowance(
         e,
@@ -249,24 +607,119 @@ fn spend_allowance(e: &Env, from: Address, spender: Address, amount: i128) {
         allowance.amount - amount,
         allowance.expiration_ledger,
     );
+    Ok(())
 }
 
 fn read_balance(e: &Env, addr: Address) -> i128 {
-    let key = DataKey::Balance(addr);
-    e.storage().persistent().get(&key).unwrap_or(0)
+    let key = DataKey::Balance(addr.clone());
+    let balance = e.storage().persistent().get(&key).unwrap_or(0);
+    if balance > 0 {
+        extend_balance_ttl(e, addr);
+    }
+    balance
 }
 
-fn receive_balance(e: &Env, addr: Address, amount: i128) {
+fn receive_balance(e: &Env, addr: Address, amount: i128) -> Result<(), ContractError> {
     let balance = read_balance(e, addr.clone());
-    let key = DataKey::Balance(addr);
-    e.storage().persistent().set(&key, &(balance + amount));
+    let new_balance = balance
+        .checked_add(amount)
+        .ok_or(ContractError::OverflowError)?;
+    let key = DataKey::Balance(addr.clone());
+    e.storage().persistent().set(&key, &new_balance);
+    extend_balance_ttl(e, addr);
+    Ok(())
 }
 
-fn spend_balance(e: &Env, addr: Address, amount: i128) {
+fn spend_balance(e: &Env, addr: Address, amount: i128) -> Result<(), ContractError> {
     let balance = read_balance(e, addr.clone());
     if balance < amount {
-        panic!("insufficient balance");
+        return Err(ContractError::BalanceError);
+    }
+    let new_balance = balance
+        .checked_sub(amount)
+        .ok_or(ContractError::OverflowError)?;
+    let key = DataKey::Balance(addr.clone());
+    e.storage().persistent().set(&key, &new_balance);
+    extend_balance_ttl(e, addr);
+    Ok(())
+}
+
+fn read_total_supply(e: &Env) -> i128 {
+    e.storage()
+        .instance()
+        .get(&DataKey::TotalSupply)
+        .unwrap_or(0)
+}
+
+fn write_total_supply(e: &Env, total_supply: i128) {
+    e.storage()
+        .instance()
+        .set(&DataKey::TotalSupply, &total_supply);
+}
+
+fn decrease_total_supply(e: &Env, amount: i128) -> Result<(), ContractError> {
+    let total_supply = read_total_supply(e)
+        .checked_sub(amount)
+        .ok_or(ContractError::OverflowError)?;
+    write_total_supply(e, total_supply);
+    Ok(())
+}
+
+fn read_minters(e: &Env) -> Vec<Address> {
+    e.storage()
+        .instance()
+        .get(&DataKey::Minters)
+        .unwrap_or(Vec::new(e))
+}
+
+fn write_minters(e: &Env, minters: &Vec<Address>) {
+    e.storage().instance().set(&DataKey::Minters, minters);
+}
+
+fn read_permission(e: &Env, owner: Address, spender: Address) -> Option<Permission> {
+    let key = DataKey::Permission(owner, spender);
+    e.storage().persistent().get(&key)
+}
+
+fn write_permission(e: &Env, owner: Address, spender: Address, permission: &Permission) {
+    let key = DataKey::Permission(owner, spender);
+    e.storage().persistent().set(&key, permission);
+    let live_for = permission
+        .expiration_ledger
+        .saturating_sub(e.ledger().sequence());
+    e.storage().persistent().extend_ttl(&key, live_for, live_for);
+}
+
+fn remove_permission(e: &Env, owner: Address, spender: Address) {
+    let key = DataKey::Permission(owner, spender);
+    e.storage().persistent().remove(&key);
+}
+
+// Consults the (owner, spender) permission record for `op`. A permission
+// authorizes the spend only while it hasn't expired, its matching operation
+// flag is set, and the requested amount fits under its remaining limit; an
+// existing permission that fails any of these checks returns an error. Only
+// when no permission has been granted at all does it fall back to the
+// classic allowance.
+fn authorize_spend(
+    e: &Env,
+    owner: Address,
+    spender: Address,
+    amount: i128,
+    op: impl Fn(&Permission) -> bool,
+) -> Result<(), ContractError> {
+    match read_permission(e, owner.clone(), spender.clone()) {
+        Some(mut permission) => {
+            if e.ledger().sequence() > permission.expiration_ledger || !op(&permission) {
+                return Err(ContractError::UnauthorizedError);
+            }
+            if permission.limit < amount {
+                return Err(ContractError::BalanceError);
+            }
+            permission.limit -= amount;
+            write_permission(e, owner, spender, &permission);
+            Ok(())
+        }
+        None => spend_allowance(e, owner, spender, amount),
     }
-    let key = DataKey::Balance(addr);
-    e.storage().persistent().set(&key, &(balance - amount));
 }
@@ -0,0 +1,324 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Ledger};
+
+fn setup() -> (Env, TokenClient<'static>, Address) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let contract_id = env.register(
+        Token,
+        (
+            admin.clone(),
+            7u32,
+            String::from_str(&env, "Stellar Lumens"),
+            String::from_str(&env, "XLM"),
+            None::<Vec<(Address, i128)>>,
+        ),
+    );
+    let client = TokenClient::new(&env, &contract_id);
+
+    (env, client, admin)
+}
+
+#[test]
+fn test_balance_survives_after_ttl_extended_on_read() {
+    let (env, client, admin) = setup();
+    let user = Address::generate(&env);
+
+    client.mint(&admin, &user, &1000);
+
+    // Advance past the bump threshold; the mint above should have already
+    // extended the entry's TTL far enough that it is still alive.
+    env.ledger().with_mut(|li| {
+        li.sequence_number += BALANCE_BUMP_THRESHOLD + 1;
+    });
+
+    assert_eq!(client.balance(&user), 1000);
+}
+
+#[test]
+fn test_allowance_survives_after_ttl_extended_on_read() {
+    let (env, client, _admin) = setup();
+    let from = Address::generate(&env);
+    let spender = Address::generate(&env);
+
+    let expiration_ledger = env.ledger().sequence() + BALANCE_BUMP_AMOUNT;
+    client.approve(&from, &spender, &500, &expiration_ledger);
+
+    env.ledger().with_mut(|li| {
+        li.sequence_number += BALANCE_BUMP_THRESHOLD - 1;
+    });
+
+    assert_eq!(client.allowance(&from, &spender), 500);
+}
+
+#[test]
+fn test_untouched_zero_balance_is_not_kept_alive() {
+    let (env, client, _admin) = setup();
+    let user = Address::generate(&env);
+
+    // An account that never received funds has no persistent entry to
+    // extend, so reading it back out just returns the default of 0.
+    env.ledger().with_mut(|li| {
+        li.sequence_number += BALANCE_BUMP_THRESHOLD + 1;
+    });
+
+    assert_eq!(client.balance(&user), 0);
+}
+
+#[test]
+fn test_constructor_initial_balances_set_total_supply() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let holder_a = Address::generate(&env);
+    let holder_b = Address::generate(&env);
+
+    let contract_id = env.register(
+        Token,
+        (
+            admin,
+            7u32,
+            String::from_str(&env, "Stellar Lumens"),
+            String::from_str(&env, "XLM"),
+            Some(soroban_sdk::vec![
+                &env,
+                (holder_a.clone(), 100i128),
+                (holder_b.clone(), 250i128)
+            ]),
+        ),
+    );
+    let client = TokenClient::new(&env, &contract_id);
+
+    assert_eq!(client.balance(&holder_a), 100);
+    assert_eq!(client.balance(&holder_b), 250);
+    assert_eq!(client.total_supply(), 350);
+}
+
+#[test]
+fn test_mint_requires_minter_role_and_updates_total_supply() {
+    let (env, client, admin) = setup();
+    let user = Address::generate(&env);
+    let stranger = Address::generate(&env);
+
+    client.mint(&admin, &user, &1000);
+    assert_eq!(client.total_supply(), 1000);
+
+    let result = client.try_mint(&stranger, &user, &500);
+    assert_eq!(result, Err(Ok(ContractError::UnauthorizedError)));
+
+    client.add_minter(&stranger);
+    client.mint(&stranger, &user, &500);
+    assert_eq!(client.total_supply(), 1500);
+
+    client.remove_minter(&stranger);
+    let result = client.try_mint(&stranger, &user, &1);
+    assert_eq!(result, Err(Ok(ContractError::UnauthorizedError)));
+}
+
+#[test]
+fn test_burn_decreases_total_supply() {
+    let (env, client, admin) = setup();
+    let user = Address::generate(&env);
+
+    client.mint(&admin, &user, &1000);
+    client.burn(&user, &400);
+
+    assert_eq!(client.total_supply(), 600);
+    assert_eq!(client.balance(&user), 600);
+}
+
+mod receiver {
+    use soroban_sdk::{contract, contractimpl, Address, Bytes, Env};
+
+    #[contract]
+    pub struct PartialAcceptReceiver;
+
+    #[contractimpl]
+    impl PartialAcceptReceiver {
+        pub fn on_token_received(_e: Env, _from: Address, amount: i128, _data: Bytes) -> i128 {
+            amount / 2
+        }
+    }
+
+    #[contract]
+    pub struct TrapReceiver;
+
+    #[contractimpl]
+    impl TrapReceiver {
+        pub fn on_token_received(_e: Env, _from: Address, _amount: i128, _data: Bytes) -> i128 {
+            panic!("refuses every deposit");
+        }
+    }
+}
+
+#[test]
+fn test_transfer_and_call_refunds_unaccepted_remainder() {
+    let (env, client, admin) = setup();
+    let from = Address::generate(&env);
+    client.mint(&admin, &from, &1000);
+
+    let vault = env.register(receiver::PartialAcceptReceiver, ());
+    let data = Bytes::new(&env);
+
+    let accepted = client.transfer_and_call(&from, &vault, &1000, &data);
+
+    assert_eq!(accepted, 500);
+    assert_eq!(client.balance(&vault), 500);
+    assert_eq!(client.balance(&from), 500);
+}
+
+#[test]
+fn test_transfer_and_call_reverts_when_recipient_traps() {
+    let (env, client, admin) = setup();
+    let from = Address::generate(&env);
+    client.mint(&admin, &from, &1000);
+
+    let vault = env.register(receiver::TrapReceiver, ());
+    let data = Bytes::new(&env);
+
+    let result = client.try_transfer_and_call(&from, &vault, &1000, &data);
+
+    assert!(result.is_err());
+    assert_eq!(client.balance(&from), 1000);
+    assert_eq!(client.balance(&vault), 0);
+}
+
+#[test]
+fn test_transfer_from_still_works_via_classic_allowance_with_no_permission() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let to = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    client.approve(&owner, &spender, &300, &expiration_ledger);
+
+    // No permission was ever granted, so this should fall back to the
+    // allowance set above and succeed exactly as it did before permissions
+    // were introduced.
+    client.transfer_from(&spender, &owner, &to, &200);
+
+    assert_eq!(client.balance(&to), 200);
+    assert_eq!(client.allowance(&owner, &spender), 100);
+}
+
+#[test]
+fn test_permission_overrides_allowance_for_transfer_from() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let to = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    client.grant_permission(&owner, &spender, &300, &expiration_ledger, &true, &false, &false);
+
+    client.transfer_from(&spender, &owner, &to, &200);
+    assert_eq!(client.balance(&to), 200);
+
+    let permission = client.query_permissions(&owner, &spender).unwrap();
+    assert_eq!(permission.limit, 100);
+
+    // Spending more than the remaining limit fails even though the
+    // account's allowance (which was never set) would otherwise be 0 too.
+    let result = client.try_transfer_from(&spender, &owner, &to, &150);
+    assert_eq!(result, Err(Ok(ContractError::BalanceError)));
+}
+
+#[test]
+fn test_permission_expiry_authorizes_nothing() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let to = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 10;
+    client.grant_permission(&owner, &spender, &300, &expiration_ledger, &true, &false, &false);
+
+    env.ledger().with_mut(|li| {
+        li.sequence_number += 11;
+    });
+
+    let result = client.try_transfer_from(&spender, &owner, &to, &1);
+    assert_eq!(result, Err(Ok(ContractError::UnauthorizedError)));
+}
+
+#[test]
+fn test_permission_flag_enforcement() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    // Granted for burning only, not transferring.
+    client.grant_permission(&owner, &spender, &300, &expiration_ledger, &false, &true, &false);
+
+    client.burn_from(&spender, &owner, &100);
+    assert_eq!(client.balance(&owner), 900);
+
+    let to = Address::generate(&env);
+    let result = client.try_transfer_from(&spender, &owner, &to, &1);
+    assert_eq!(result, Err(Ok(ContractError::UnauthorizedError)));
+}
+
+#[test]
+fn test_revoked_permission_authorizes_nothing() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let to = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    client.grant_permission(&owner, &spender, &300, &expiration_ledger, &true, &false, &false);
+    client.revoke_permission(&owner, &spender);
+
+    assert!(client.query_permissions(&owner, &spender).is_none());
+
+    // No permission and no allowance either, so this should fail.
+    let result = client.try_transfer_from(&spender, &owner, &to, &1);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_permission_can_approve_delegates_a_fresh_allowance() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let delegate = Address::generate(&env);
+    let new_spender = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    client.grant_permission(&owner, &delegate, &300, &expiration_ledger, &false, &false, &true);
+
+    client.approve_from(&delegate, &owner, &new_spender, &200, &expiration_ledger);
+    assert_eq!(client.allowance(&owner, &new_spender), 200);
+
+    // Granting an allowance doesn't spend the owner's funds, so the
+    // permission's spend limit is untouched.
+    let permission = client.query_permissions(&owner, &delegate).unwrap();
+    assert_eq!(permission.limit, 300);
+}
+
+#[test]
+fn test_permission_without_can_approve_cannot_delegate_allowance() {
+    let (env, client, admin) = setup();
+    let owner = Address::generate(&env);
+    let delegate = Address::generate(&env);
+    let new_spender = Address::generate(&env);
+    client.mint(&admin, &owner, &1000);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    client.grant_permission(&owner, &delegate, &300, &expiration_ledger, &true, &true, &false);
+
+    let result = client.try_approve_from(&delegate, &owner, &new_spender, &200, &expiration_ledger);
+    assert_eq!(result, Err(Ok(ContractError::UnauthorizedError)));
+}